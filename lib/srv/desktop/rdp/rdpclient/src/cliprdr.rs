@@ -0,0 +1,521 @@
+// Copyright 2021 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module implements the `cliprdr` static virtual channel ([MS-RDPECLIP]), which carries
+//! clipboard synchronization between the client and the RDP server.
+//!
+//! The clipboard bridge is bidirectional and multi-format, mirroring the "data device" model of
+//! [MS-RDPECLIP] where either peer can offer its selection in more than one format and the other
+//! side picks which one it wants:
+//!
+//! - Client→server: `update_clipboard` is handed every format Go currently has on the local
+//!   clipboard (`CF_UNICODETEXT`, `CF_DIB`/`CF_DIBV5` images, the registered `HTML Format`) and
+//!   advertises all of them in one Format List PDU. Each is cached so whichever one the server
+//!   asks for via a Format Data Request can be answered immediately. `CF_HDROP` (via the virtual
+//!   `FileGroupDescriptorW` format) is advertised the same way when file-transfer callbacks are
+//!   configured, but — like the shared-directory traffic on `rdpdr` — its descriptor list and file
+//!   contents live on Go's side of the FFI boundary, so those two are fetched lazily with an async
+//!   request/response pair instead of being cached up front: a Format Data Request for
+//!   `FileGroupDescriptorW` is forwarded via `tdp_clip_file_list_request` and answered later
+//!   through `handle_tdp_clip_file_list_response`; a File Contents Request (SIZE or RANGE) is
+//!   forwarded via `tdp_clip_file_read_request` and answered through
+//!   `handle_tdp_clip_file_read_response`.
+//! - Server→client: the server's Format List PDU is surfaced to Go as-is via
+//!   `on_remote_clipboard`, format IDs and registered format names included, so Go (not this
+//!   module) decides whether and what to paste. Once Go picks a format, `request_remote_format`
+//!   issues the Format Data Request for it; the response arrives as raw [`ClipboardData`] through
+//!   `on_remote_clipboard_data`, DIB payloads included with their `BITMAPINFOHEADER` intact so Go
+//!   can convert them to PNG itself.
+
+use crate::errors::try_error;
+use rdp::core::mcs;
+use rdp::model::error::RdpResult;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+pub const CHANNEL_NAME: &str = "cliprdr";
+
+/// Clipboard format IDs relevant to this client. `CF_UNICODETEXT`, `CF_DIB`, and `CF_DIBV5` are
+/// standard Win32 clipboard formats; `FileGroupDescriptorW` and `HTML Format` are registered
+/// formats negotiated by name, backing `CF_HDROP` (file copy) and HTML fragments respectively.
+pub const CF_UNICODETEXT: u32 = 13;
+pub const CF_DIB: u32 = 8;
+pub const CF_DIBV5: u32 = 17;
+pub const FILE_GROUP_DESCRIPTORW: &str = "FileGroupDescriptorW";
+pub const HTML_FORMAT: &str = "HTML Format";
+/// The registered format IDs we advertise `FileGroupDescriptorW` and `HTML Format` under. Real
+/// RDPECLIP peers negotiate registered format IDs dynamically starting at 0xC000; since we're the
+/// only client these PDUs ever round-trip through, a pair of fixed IDs above the standard CF_*
+/// range is enough.
+const FILE_GROUP_DESCRIPTORW_ID: u32 = 0xC001;
+const HTML_FORMAT_ID: u32 = 0xC002;
+
+/// File Contents Request `dwFlags` values: request the file's size, or a byte range of its
+/// contents.
+pub const FILECONTENTS_SIZE: u32 = 0x0000_0001;
+pub const FILECONTENTS_RANGE: u32 = 0x0000_0002;
+
+/// Metadata for one file being offered in a `CF_HDROP` clipboard transfer.
+#[derive(Debug, Clone)]
+pub struct FileDescriptor {
+    pub name: String,
+    pub size: u64,
+    pub is_directory: bool,
+}
+
+/// The server asked for the `FileGroupDescriptorW` descriptor list for the files currently on the
+/// local clipboard; Go answers with [`ClipDataFileListResponse`].
+#[derive(Debug, Clone)]
+pub struct ClipDataFileListRequest {
+    pub stream_id: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClipDataFileListResponse {
+    pub stream_id: u32,
+    pub files: Vec<FileDescriptor>,
+}
+
+/// The server asked for a file's size (`SIZE`) or a byte range of its contents (`RANGE`); Go
+/// answers with [`ClipDataFileReadResponse`] once the bytes (or size) are available.
+#[derive(Debug, Clone)]
+pub struct ClipDataFileReadRequest {
+    pub stream_id: u32,
+    pub list_index: u32,
+    pub dw_flags: u32,
+    pub offset: u64,
+    pub length: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClipDataFileReadResponse {
+    pub stream_id: u32,
+    /// The file's total size, when answering a `SIZE` request.
+    pub size: u64,
+    /// The requested byte range, when answering a `RANGE` request.
+    pub data: Vec<u8>,
+}
+
+/// One format's worth of clipboard content, in either direction: the raw bytes Go hands us for
+/// `update_clipboard`, or the raw bytes we hand back to Go out of a Format Data Response. A DIB
+/// payload's `data` is the format's wire bytes verbatim, `BITMAPINFOHEADER` included, since only
+/// Go knows how to turn that into something displayable.
+#[derive(Debug, Clone)]
+pub struct ClipboardData {
+    pub format_id: u32,
+    pub data: Vec<u8>,
+}
+
+/// One format the server announced it currently has on its clipboard, from its Format List PDU.
+#[derive(Debug, Clone)]
+pub struct RemoteClipboardFormat {
+    pub format_id: u32,
+    /// Present for registered (non-standard) formats, e.g. `HTML Format`; `None` for a standard
+    /// `CF_*` format, which is identified by `format_id` alone.
+    pub name: Option<String>,
+}
+
+type RemoteFormatListCallback = Box<dyn Fn(Vec<RemoteClipboardFormat>) -> RdpResult<()>>;
+type RemoteFormatDataCallback = Box<dyn Fn(ClipboardData) -> RdpResult<()>>;
+type FileListReqCallback = Box<dyn Fn(ClipDataFileListRequest) -> RdpResult<()>>;
+type FileReadReqCallback = Box<dyn Fn(ClipDataFileReadRequest) -> RdpResult<()>>;
+
+/// Config carries the boxed callbacks `connect_rdp_inner` wires up for the clipboard bridge: two
+/// for the server→client direction (the remote's announced formats, and the data once Go picks
+/// one), and two more paralleling the `rdpdr` shared-directory callbacks for the `CF_HDROP`
+/// file-transfer path.
+pub struct Config {
+    pub on_remote_clipboard: RemoteFormatListCallback,
+    pub on_remote_clipboard_data: RemoteFormatDataCallback,
+    pub tdp_clip_file_list_request: Option<FileListReqCallback>,
+    pub tdp_clip_file_read_request: Option<FileReadReqCallback>,
+}
+
+/// A File Contents Request we've forwarded to Go and are waiting on a response for, keyed by the
+/// stream ID the server used so the eventual response can be routed back to the right PDU.
+struct PendingFileRead {
+    dw_flags: u32,
+}
+
+/// Client is the Rust side of the `cliprdr` channel.
+pub struct Client {
+    on_remote_clipboard: RemoteFormatListCallback,
+    on_remote_clipboard_data: RemoteFormatDataCallback,
+    tdp_clip_file_list_request: Option<FileListReqCallback>,
+    tdp_clip_file_read_request: Option<FileReadReqCallback>,
+    pending_file_reads: HashMap<u32, PendingFileRead>,
+    next_stream_id: u32,
+    /// This client's own clipboard contents, by format, as last reported through
+    /// `update_clipboard`. Answers the server's Format Data Requests for any format we advertised
+    /// other than `FileGroupDescriptorW`, which is instead fetched lazily from Go.
+    local_formats: HashMap<u32, Vec<u8>>,
+    /// The format ID of the Format Data Request we most recently sent the server via
+    /// `request_remote_format`, so the eventual Format Data Response can be tagged with it.
+    pending_remote_format: Option<u32>,
+}
+
+impl Client {
+    pub fn new(cfg: Config) -> Self {
+        Self {
+            on_remote_clipboard: cfg.on_remote_clipboard,
+            on_remote_clipboard_data: cfg.on_remote_clipboard_data,
+            tdp_clip_file_list_request: cfg.tdp_clip_file_list_request,
+            tdp_clip_file_read_request: cfg.tdp_clip_file_read_request,
+            pending_file_reads: HashMap::new(),
+            next_stream_id: 1,
+            local_formats: HashMap::new(),
+            pending_remote_format: None,
+        }
+    }
+
+    /// Called when Go has new local clipboard contents to advertise to the server, one entry per
+    /// format currently available (e.g. `CF_UNICODETEXT` text alongside a `CF_DIB` image). If
+    /// file-transfer callbacks are configured, `CF_HDROP` is advertised alongside them and its
+    /// descriptor list is fetched lazily the next time the server asks for it.
+    pub fn update_clipboard(
+        &mut self,
+        items: Vec<ClipboardData>,
+    ) -> RdpResult<Vec<rdp::model::data::Message>> {
+        let format_ids: Vec<u32> = items.iter().map(|item| item.format_id).collect();
+        self.local_formats = items.into_iter().map(|item| (item.format_id, item.data)).collect();
+        Ok(vec![format_list_pdu(
+            &format_ids,
+            self.tdp_clip_file_list_request.is_some(),
+        )])
+    }
+
+    /// Issues a Format Data Request for `format_id`, one the server previously announced in its
+    /// Format List PDU. The response arrives asynchronously via `on_remote_clipboard_data`.
+    pub fn request_remote_format<S: std::io::Read + std::io::Write>(
+        &mut self,
+        format_id: u32,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        self.pending_remote_format = Some(format_id);
+        mcs.write(&CHANNEL_NAME.to_string(), format_data_request_pdu(format_id))
+    }
+
+    pub fn read_and_reply<S: std::io::Read + std::io::Write>(
+        &mut self,
+        payload: rdp::model::data::Message,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        let mut payload = Cursor::new(payload);
+        let msg_type = read_u16(&mut payload)?;
+        skip(&mut payload, 6)?; // msgFlags, dataLen: neither is needed by the handlers below
+        match msg_type {
+            CB_FORMAT_LIST => self.handle_format_list(&mut payload, mcs),
+            CB_FORMAT_DATA_REQUEST => self.handle_format_data_request(&mut payload, mcs),
+            CB_FORMAT_DATA_RESPONSE => self.handle_format_data_response(&mut payload),
+            CB_FILECONTENTS_REQUEST => self.handle_file_contents_request(&mut payload, mcs),
+            _ => {
+                debug!("cliprdr: ignoring unsupported message type {:#06x}", msg_type);
+                Ok(())
+            }
+        }
+    }
+
+    /// The server announced new clipboard contents. Parse the format list it offered and surface
+    /// it to Go as-is via `on_remote_clipboard` — which format (if any) to fetch is Go's call, made
+    /// later through `request_remote_format` — then acknowledge with a Format List Response, which
+    /// [MS-RDPECLIP] requires before the server will answer any Format Data Request of ours.
+    fn handle_format_list<S: std::io::Read + std::io::Write>(
+        &mut self,
+        payload: &mut Cursor<rdp::model::data::Message>,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        let mut formats = Vec::new();
+        while (payload.position() as usize) < payload.get_ref().len() {
+            let format_id = read_u32(payload)?;
+            let name_len = read_u32(payload)? as usize;
+            let name = read_utf16_string(payload, name_len)?;
+            formats.push(RemoteClipboardFormat {
+                format_id,
+                name: if name.is_empty() { None } else { Some(name) },
+            });
+        }
+        (self.on_remote_clipboard)(formats)?;
+        mcs.write(&CHANNEL_NAME.to_string(), format_list_response_pdu())
+    }
+
+    /// The server answered our Format Data Request with the bytes for whichever format
+    /// `request_remote_format` most recently asked for; hand them to Go untouched via
+    /// `on_remote_clipboard_data`, tagged with that format ID.
+    fn handle_format_data_response(
+        &mut self,
+        payload: &mut Cursor<rdp::model::data::Message>,
+    ) -> RdpResult<()> {
+        let format_id = self
+            .pending_remote_format
+            .take()
+            .ok_or_else(|| try_error("Format Data Response without a pending request"))?;
+        let mut data = Vec::new();
+        payload
+            .read_to_end(&mut data)
+            .map_err(|_| try_error("cliprdr: unexpected end of PDU"))?;
+        (self.on_remote_clipboard_data)(ClipboardData { format_id, data })
+    }
+
+    /// The server wants the data for a format we advertised. `FileGroupDescriptorW` lives on Go's
+    /// side and is fetched asynchronously; every other format we advertised was cached up front by
+    /// `update_clipboard`, so it's answered immediately.
+    fn handle_format_data_request<S: std::io::Read + std::io::Write>(
+        &mut self,
+        payload: &mut Cursor<rdp::model::data::Message>,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        let requested_format_id = read_u32(payload)?;
+        if requested_format_id == FILE_GROUP_DESCRIPTORW_ID {
+            if let Some(list_request) = &self.tdp_clip_file_list_request {
+                let stream_id = self.next_stream_id;
+                self.next_stream_id = self.next_stream_id.wrapping_add(1);
+                return list_request(ClipDataFileListRequest { stream_id });
+            }
+            return Ok(());
+        }
+        if let Some(data) = self.local_formats.get(&requested_format_id) {
+            return mcs.write(&CHANNEL_NAME.to_string(), format_data_response_pdu(data));
+        }
+        debug!(
+            "cliprdr: ignoring Format Data Request for unadvertised format {:#x}",
+            requested_format_id
+        );
+        Ok(())
+    }
+
+    /// Handles both File Contents Request modes: SIZE (report a file's length) and RANGE (stream
+    /// a byte range of its contents), forwarding the request to Go and answering asynchronously
+    /// once [`Client::handle_tdp_clip_file_read_response`] is called.
+    fn handle_file_contents_request<S: std::io::Read + std::io::Write>(
+        &mut self,
+        payload: &mut Cursor<rdp::model::data::Message>,
+        _mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        let Some(read_request) = &self.tdp_clip_file_read_request else {
+            return Ok(());
+        };
+        let server_stream_id = read_u32(payload)?;
+        let list_index = read_u32(payload)?;
+        let dw_flags = read_u32(payload)?;
+        let offset = read_u64(payload)?;
+        let length = read_u32(payload)?;
+
+        self.pending_file_reads
+            .insert(server_stream_id, PendingFileRead { dw_flags });
+
+        read_request(ClipDataFileReadRequest {
+            stream_id: server_stream_id,
+            list_index,
+            dw_flags,
+            offset,
+            length,
+        })
+    }
+
+    /// Go has the `FileGroupDescriptorW` descriptor list ready; send the Format Data Response.
+    pub fn handle_tdp_clip_file_list_response<S: std::io::Read + std::io::Write>(
+        &mut self,
+        res: ClipDataFileListResponse,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        mcs.write(
+            &CHANNEL_NAME.to_string(),
+            file_descriptor_list_pdu(&res.files),
+        )
+    }
+
+    /// Go has the requested file size or byte range ready; send the matching File Contents
+    /// Response.
+    pub fn handle_tdp_clip_file_read_response<S: std::io::Read + std::io::Write>(
+        &mut self,
+        res: ClipDataFileReadResponse,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        let pending = self
+            .pending_file_reads
+            .remove(&res.stream_id)
+            .ok_or_else(|| try_error("File Contents Response for unknown stream id"))?;
+
+        let pdu = if pending.dw_flags & FILECONTENTS_SIZE != 0 {
+            file_contents_size_response(res.stream_id, res.size)
+        } else if pending.dw_flags & FILECONTENTS_RANGE != 0 {
+            file_contents_range_response(res.stream_id, &res.data)
+        } else {
+            return Err(try_error("pending File Contents Request had no known flag set"));
+        };
+        mcs.write(&CHANNEL_NAME.to_string(), pdu)
+    }
+}
+
+const CB_FORMAT_LIST: u16 = 0x0002;
+const CB_FORMAT_LIST_RESPONSE: u16 = 0x0003;
+const CB_FORMAT_DATA_REQUEST: u16 = 0x0004;
+const CB_FORMAT_DATA_RESPONSE: u16 = 0x0005;
+const CB_FILECONTENTS_REQUEST: u16 = 0x0008;
+const CB_FILECONTENTS_RESPONSE: u16 = 0x0009;
+/// msgFlags value shared by every response PDU this client sends.
+const CB_RESPONSE_OK: u16 = 0x0001;
+
+fn read_u16(c: &mut Cursor<rdp::model::data::Message>) -> RdpResult<u16> {
+    let mut buf = [0u8; 2];
+    c.read_exact(&mut buf)
+        .map_err(|_| try_error("cliprdr: unexpected end of PDU"))?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(c: &mut Cursor<rdp::model::data::Message>) -> RdpResult<u32> {
+    let mut buf = [0u8; 4];
+    c.read_exact(&mut buf)
+        .map_err(|_| try_error("cliprdr: unexpected end of PDU"))?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(c: &mut Cursor<rdp::model::data::Message>) -> RdpResult<u64> {
+    let mut buf = [0u8; 8];
+    c.read_exact(&mut buf)
+        .map_err(|_| try_error("cliprdr: unexpected end of PDU"))?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn skip(c: &mut Cursor<rdp::model::data::Message>, n: usize) -> RdpResult<()> {
+    let mut buf = vec![0u8; n];
+    c.read_exact(&mut buf)
+        .map_err(|_| try_error("cliprdr: unexpected end of PDU"))?;
+    Ok(())
+}
+
+/// Reads a UTF-16LE string given its length in bytes (as opposed to [`skip`]'s plain byte count),
+/// the encoding every registered-format name in this file is carried in.
+fn read_utf16_string(c: &mut Cursor<rdp::model::data::Message>, byte_len: usize) -> RdpResult<String> {
+    let mut buf = vec![0u8; byte_len];
+    c.read_exact(&mut buf)
+        .map_err(|_| try_error("cliprdr: unexpected end of PDU"))?;
+    Ok(String::from_utf16_lossy(
+        &buf.chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect::<Vec<u16>>(),
+    ))
+}
+
+/// The registered format name to advertise a format under in a Format List PDU entry, if any;
+/// standard `CF_*` formats need none.
+fn format_name_for(format_id: u32) -> Option<&'static str> {
+    match format_id {
+        FILE_GROUP_DESCRIPTORW_ID => Some(FILE_GROUP_DESCRIPTORW),
+        HTML_FORMAT_ID => Some(HTML_FORMAT),
+        _ => None,
+    }
+}
+
+fn push_format_list_entry(body: &mut Vec<u8>, format_id: u32) {
+    body.extend_from_slice(&format_id.to_le_bytes());
+    let utf16: Vec<u8> = format_name_for(format_id)
+        .unwrap_or("")
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    body.extend_from_slice(&(utf16.len() as u32).to_le_bytes());
+    body.extend_from_slice(&utf16);
+}
+
+fn format_list_pdu(formats: &[u32], advertise_files: bool) -> rdp::model::data::Message {
+    let mut body = Vec::new();
+    for &format_id in formats {
+        push_format_list_entry(&mut body, format_id);
+    }
+    if advertise_files {
+        push_format_list_entry(&mut body, FILE_GROUP_DESCRIPTORW_ID);
+    }
+
+    let mut out = Vec::with_capacity(8 + body.len());
+    out.extend_from_slice(&CB_FORMAT_LIST.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // msgFlags
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+fn format_list_response_pdu() -> rdp::model::data::Message {
+    let mut out = Vec::with_capacity(8);
+    out.extend_from_slice(&CB_FORMAT_LIST_RESPONSE.to_le_bytes());
+    out.extend_from_slice(&CB_RESPONSE_OK.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes()); // dataLen
+    out
+}
+
+fn format_data_request_pdu(format_id: u32) -> rdp::model::data::Message {
+    let mut body = Vec::with_capacity(4);
+    body.extend_from_slice(&format_id.to_le_bytes());
+
+    let mut out = Vec::with_capacity(8 + body.len());
+    out.extend_from_slice(&CB_FORMAT_DATA_REQUEST.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+fn format_data_response_pdu(data: &[u8]) -> rdp::model::data::Message {
+    let mut out = Vec::with_capacity(8 + data.len());
+    out.extend_from_slice(&CB_FORMAT_DATA_RESPONSE.to_le_bytes());
+    out.extend_from_slice(&CB_RESPONSE_OK.to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+fn file_descriptor_list_pdu(files: &[FileDescriptor]) -> rdp::model::data::Message {
+    let mut body = Vec::new();
+    body.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for f in files {
+        body.extend_from_slice(&(f.is_directory as u32).to_le_bytes());
+        body.extend_from_slice(&f.size.to_le_bytes());
+        let name: Vec<u8> = f.name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        body.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        body.extend_from_slice(&name);
+    }
+
+    let mut out = Vec::with_capacity(8 + body.len());
+    out.extend_from_slice(&CB_FORMAT_DATA_RESPONSE.to_le_bytes());
+    out.extend_from_slice(&CB_RESPONSE_OK.to_le_bytes());
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+fn file_contents_size_response(stream_id: u32, size: u64) -> rdp::model::data::Message {
+    let mut body = Vec::with_capacity(12);
+    body.extend_from_slice(&stream_id.to_le_bytes());
+    body.extend_from_slice(&size.to_le_bytes());
+
+    let mut out = Vec::with_capacity(8 + body.len());
+    out.extend_from_slice(&CB_FILECONTENTS_RESPONSE.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // msgFlags: CB_RESPONSE_OK
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+fn file_contents_range_response(stream_id: u32, data: &[u8]) -> rdp::model::data::Message {
+    let mut body = Vec::with_capacity(4 + data.len());
+    body.extend_from_slice(&stream_id.to_le_bytes());
+    body.extend_from_slice(data);
+
+    let mut out = Vec::with_capacity(8 + body.len());
+    out.extend_from_slice(&CB_FILECONTENTS_RESPONSE.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
@@ -0,0 +1,166 @@
+// Copyright 2022 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decodes the server-pushed RDP pointer (cursor) shape updates — Color Pointer, New Pointer,
+//! Cached Pointer, and System Pointer — into straight RGBA bitmaps `lib.rs` hands to Go through
+//! `handle_cursor`.
+//!
+//! Color and New Pointer PDUs carry an AND (monochrome) mask and an XOR color mask, both
+//! bottom-up and padded to 2-byte row alignment; [`decode_pointer_shape`] flips them to top-down
+//! RGBA. [`CursorCache`] remembers shapes by `cacheIndex` so a later Cached Pointer PDU can
+//! re-emit one without us re-decoding it.
+
+use std::collections::HashMap;
+
+/// A decoded cursor shape, in straight top-down RGBA. A zero `width`/`height` (see
+/// [`CursorShape::hidden`]) means "hide the cursor."
+#[derive(Clone)]
+pub struct CursorShape {
+    pub hotspot_x: u16,
+    pub hotspot_y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub rgba: Vec<u8>,
+}
+
+impl CursorShape {
+    /// The shape a System Pointer (Hidden) PDU maps to.
+    pub fn hidden() -> Self {
+        Self {
+            hotspot_x: 0,
+            hotspot_y: 0,
+            width: 0,
+            height: 0,
+            rgba: Vec::new(),
+        }
+    }
+}
+
+/// Decodes a Color Pointer PDU (`xor_bpp` is always 24) or New Pointer PDU (`xor_bpp` given on
+/// the wire) into a top-down RGBA [`CursorShape`].
+///
+/// Per the AND/XOR compositing rule: where the AND-mask bit is set and the XOR pixel is black,
+/// the pixel is fully transparent (the real cursor is an XOR/invert over the destination, which
+/// has no faithful RGBA equivalent, so a non-black XOR pixel under a set AND bit is approximated
+/// as an opaque pixel of that color instead). Where the AND-mask bit is clear, the XOR pixel is
+/// opaque.
+pub fn decode_pointer_shape(
+    hotspot_x: u16,
+    hotspot_y: u16,
+    width: u16,
+    height: u16,
+    xor_bpp: u16,
+    and_mask: &[u8],
+    xor_mask: &[u8],
+) -> CursorShape {
+    let w = width as usize;
+    let h = height as usize;
+    let xor_row_bytes = if xor_bpp == 1 {
+        (w + 7) / 8
+    } else {
+        w * bytes_per_pixel(xor_bpp)
+    };
+    let xor_stride = row_stride(xor_row_bytes);
+    let and_stride = row_stride((w + 7) / 8);
+
+    let mut rgba = vec![0u8; w * h * 4];
+    for y in 0..h {
+        // The masks are stored bottom-up; we decode top-down.
+        let src_row = h - 1 - y;
+        for x in 0..w {
+            let and_bit = and_mask
+                .get(src_row * and_stride + x / 8)
+                .map(|byte| (byte >> (7 - x % 8)) & 1 == 1)
+                .unwrap_or(true);
+            let (r, g, b) = if xor_bpp == 1 {
+                read_xor_pixel_1bpp(xor_mask, src_row * xor_stride, x)
+            } else {
+                read_xor_pixel(
+                    xor_mask,
+                    src_row * xor_stride + x * bytes_per_pixel(xor_bpp),
+                    xor_bpp,
+                )
+            };
+            let is_black = r == 0 && g == 0 && b == 0;
+
+            let dst = (y * w + x) * 4;
+            rgba[dst] = r;
+            rgba[dst + 1] = g;
+            rgba[dst + 2] = b;
+            rgba[dst + 3] = if and_bit && is_black { 0 } else { 255 };
+        }
+    }
+
+    CursorShape {
+        hotspot_x,
+        hotspot_y,
+        width,
+        height,
+        rgba,
+    }
+}
+
+fn bytes_per_pixel(bpp: u16) -> usize {
+    ((bpp as usize) + 7) / 8
+}
+
+fn row_stride(bytes_per_row: usize) -> usize {
+    (bytes_per_row + 1) & !1
+}
+
+// Monochrome (New Pointer, xor_bpp == 1) XOR masks are bit-packed one bit per pixel, same
+// as the AND mask: `row_offset` is the start of the row in bytes, `x` the pixel's column.
+fn read_xor_pixel_1bpp(xor_mask: &[u8], row_offset: usize, x: usize) -> (u8, u8, u8) {
+    let v = xor_mask
+        .get(row_offset + x / 8)
+        .map(|byte| (byte >> (7 - x % 8)) & 1 == 1)
+        .unwrap_or(false);
+    let v = if v { 255 } else { 0 };
+    (v, v, v)
+}
+
+fn read_xor_pixel(xor_mask: &[u8], offset: usize, xor_bpp: u16) -> (u8, u8, u8) {
+    match xor_bpp {
+        // 24/32bpp XOR masks both store color as packed little-endian BGR(X); we only need the
+        // first three bytes regardless of whether a 4th (unused) alpha/padding byte follows.
+        24 | 32 => {
+            let b = xor_mask.get(offset).copied().unwrap_or(0);
+            let g = xor_mask.get(offset + 1).copied().unwrap_or(0);
+            let r = xor_mask.get(offset + 2).copied().unwrap_or(0);
+            (r, g, b)
+        }
+        _ => (0, 0, 0),
+    }
+}
+
+/// Remembers decoded cursor shapes by `cacheIndex` so a Cached Pointer PDU can re-emit a shape the
+/// server previously sent as a Color or New Pointer PDU.
+#[derive(Default)]
+pub struct CursorCache {
+    shapes: HashMap<u16, CursorShape>,
+}
+
+impl CursorCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, cache_index: u16, shape: CursorShape) {
+        self.shapes.insert(cache_index, shape);
+    }
+
+    pub fn get(&self, cache_index: u16) -> Option<&CursorShape> {
+        self.shapes.get(&cache_index)
+    }
+}
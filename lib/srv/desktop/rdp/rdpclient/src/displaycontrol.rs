@@ -0,0 +1,247 @@
+// Copyright 2023 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module implements the `Microsoft::Windows::RDS::DisplayControl` dynamic virtual channel
+//! ([MS-RDPEDISP]), which lets the client renegotiate the session resolution at runtime instead
+//! of being fixed at connect time.
+//!
+//! Unlike the static virtual channels in `rdpdr`/`cliprdr`/`rdpsnd`, DisplayControl is a *dynamic*
+//! virtual channel: it's multiplexed over the `drdynvc` static channel and only exists once the
+//! server creates it with a DVC Create Request naming it, which this client must accept with a
+//! DVC Create Response before it can send a DISPLAYCONTROL_MONITOR_LAYOUT_PDU on it.
+//!
+//! `Client` remembers the last size it successfully sent (`last_size`) for two reasons: `resize`
+//! skips re-sending a layout the server already has, and a `Client` built across a redial with
+//! `new`'s `restore_size` resends that size itself as soon as the DVC comes back up, so a
+//! transient drop doesn't reset the session back to its original resolution.
+
+use crate::errors::try_error;
+use rdp::core::mcs;
+use rdp::model::error::RdpResult;
+use std::io::{Cursor, Read};
+
+/// The static channel DisplayControl (and every other DVC) is carried over.
+pub const DVC_TRANSPORT_CHANNEL_NAME: &str = "drdynvc";
+
+/// The DVC name the server creates for display control.
+pub const CHANNEL_NAME: &str = "Microsoft::Windows::RDS::DisplayControl";
+
+/// The pixel dimensions, per [MS-RDPEDISP], a monitor layout can specify.
+const MIN_DIMENSION: u32 = 200;
+const MAX_DIMENSION: u32 = 8192;
+
+const DVC_CMD_CREATE_REQUEST: u8 = 0x01;
+const DVC_CMD_CREATE_RESPONSE: u8 = 0x01;
+const DVC_CMD_DATA: u8 = 0x03;
+
+const DISPLAYCONTROL_PDU_TYPE_MONITOR_LAYOUT: u32 = 0x0002;
+
+/// The last monitor layout successfully negotiated with the server, so a later reconnect can
+/// restore it instead of falling back to the size the session was originally opened at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DesktopSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Client is the Rust side of the DisplayControl DVC.
+pub struct Client {
+    /// The DVC channel ID the server granted us in its Create Response, once the DVC exists.
+    /// `resize` can't send anything until this is set.
+    channel_id: Option<u32>,
+    last_size: Option<DesktopSize>,
+}
+
+impl Client {
+    /// `restore_size`, if given, is the last size `resize` successfully negotiated on a prior
+    /// connection that `spawn_rdp_reader` is now redialing after a transient drop: once the
+    /// server (re-)creates the DisplayControl DVC, this client resends that layout itself,
+    /// restoring the resolution without Go having to call `write_rdp_resize` again.
+    pub fn new(restore_size: Option<DesktopSize>) -> Self {
+        Self {
+            channel_id: None,
+            last_size: restore_size,
+        }
+    }
+
+    pub fn last_size(&self) -> Option<DesktopSize> {
+        self.last_size
+    }
+
+    /// Handles traffic arriving on the `drdynvc` static channel, accepting a Create Request for
+    /// our DVC and ignoring traffic for any other DVC the server may have opened.
+    pub fn read_and_reply<S: std::io::Read + std::io::Write>(
+        &mut self,
+        payload: rdp::model::data::Message,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        let mut payload = Cursor::new(payload);
+        let header = read_u8(&mut payload)?;
+        let cmd = header >> 4;
+        let channel_id = read_u32(&mut payload)?;
+
+        match cmd {
+            DVC_CMD_CREATE_REQUEST => self.handle_create_request(channel_id, &mut payload, mcs),
+            DVC_CMD_DATA => {
+                debug!("displaycontrol: ignoring unexpected server-to-client DVC data");
+                Ok(())
+            }
+            _ => {
+                debug!("displaycontrol: ignoring unsupported DVC command {:#x}", cmd);
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_create_request<S: std::io::Read + std::io::Write>(
+        &mut self,
+        channel_id: u32,
+        payload: &mut Cursor<rdp::model::data::Message>,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        let mut name = Vec::new();
+        payload
+            .read_to_end(&mut name)
+            .map_err(|_| try_error("displaycontrol: truncated DVC Create Request"))?;
+        // The channel name is a null-terminated ASCII string.
+        let name = String::from_utf8_lossy(
+            name.split(|&b| b == 0).next().unwrap_or_default(),
+        )
+        .into_owned();
+
+        if name != CHANNEL_NAME {
+            return Ok(());
+        }
+
+        self.channel_id = Some(channel_id);
+        mcs.write(
+            &DVC_TRANSPORT_CHANNEL_NAME.to_string(),
+            create_response_pdu(channel_id),
+        )?;
+
+        // Restore the size from before a redial, if any, now that the DVC exists to send it on.
+        // Bypasses resize()'s debounce: last_size is already set to this value, but nothing has
+        // actually told the server on this connection yet.
+        if let Some(size) = self.last_size {
+            mcs.write(
+                &DVC_TRANSPORT_CHANNEL_NAME.to_string(),
+                data_pdu(channel_id, &monitor_layout_pdu(size.width, size.height)),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Sends a DISPLAYCONTROL_MONITOR_LAYOUT_PDU describing one primary monitor at
+    /// `width`x`height`, failing if the DVC hasn't been created yet or the dimensions are outside
+    /// what [MS-RDPEDISP] allows (even, and within `[200, 8192]`). A no-op if `width`x`height`
+    /// already matches the last size successfully sent, since the server already has it.
+    pub fn resize<S: std::io::Read + std::io::Write>(
+        &mut self,
+        width: u32,
+        height: u32,
+        mcs: &mut mcs::Client<S>,
+    ) -> RdpResult<()> {
+        let channel_id = self
+            .channel_id
+            .ok_or_else(|| try_error("cannot resize before DisplayControl DVC is established"))?;
+
+        if width % 2 != 0 || height % 2 != 0 {
+            return Err(try_error("resize dimensions must be even"));
+        }
+        if !(MIN_DIMENSION..=MAX_DIMENSION).contains(&width)
+            || !(MIN_DIMENSION..=MAX_DIMENSION).contains(&height)
+        {
+            return Err(try_error(&format!(
+                "resize dimensions must be within [{}, {}]",
+                MIN_DIMENSION, MAX_DIMENSION
+            )));
+        }
+
+        let size = DesktopSize { width, height };
+        if self.last_size == Some(size) {
+            return Ok(());
+        }
+
+        mcs.write(
+            &DVC_TRANSPORT_CHANNEL_NAME.to_string(),
+            data_pdu(channel_id, &monitor_layout_pdu(width, height)),
+        )?;
+        self.last_size = Some(size);
+        Ok(())
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+fn read_u8(c: &mut Cursor<rdp::model::data::Message>) -> RdpResult<u8> {
+    let mut buf = [0u8; 1];
+    c.read_exact(&mut buf)
+        .map_err(|_| try_error("displaycontrol: unexpected end of PDU"))?;
+    Ok(buf[0])
+}
+
+fn read_u32(c: &mut Cursor<rdp::model::data::Message>) -> RdpResult<u32> {
+    let mut buf = [0u8; 4];
+    c.read_exact(&mut buf)
+        .map_err(|_| try_error("displaycontrol: unexpected end of PDU"))?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Builds the DVC header (cmd nibble + channel ID) that every PDU on `drdynvc` is framed with.
+fn dvc_header(cmd: u8, channel_id: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(5);
+    out.push(cmd << 4);
+    out.extend_from_slice(&channel_id.to_le_bytes());
+    out
+}
+
+fn create_response_pdu(channel_id: u32) -> rdp::model::data::Message {
+    let mut out = dvc_header(DVC_CMD_CREATE_RESPONSE, channel_id);
+    out.extend_from_slice(&0u32.to_le_bytes()); // creationStatus: STATUS_SUCCESS
+    out
+}
+
+fn data_pdu(channel_id: u32, body: &[u8]) -> rdp::model::data::Message {
+    let mut out = dvc_header(DVC_CMD_DATA, channel_id);
+    out.extend_from_slice(body);
+    out
+}
+
+/// Builds a DISPLAYCONTROL_MONITOR_LAYOUT_PDU describing a single primary monitor at the given
+/// pixel dimensions.
+fn monitor_layout_pdu(width: u32, height: u32) -> Vec<u8> {
+    let mut monitor = Vec::new();
+    monitor.extend_from_slice(&0x01u32.to_le_bytes()); // Flags: DISPLAYCONTROL_MONITOR_PRIMARY
+    monitor.extend_from_slice(&0u32.to_le_bytes()); // Left
+    monitor.extend_from_slice(&0u32.to_le_bytes()); // Top
+    monitor.extend_from_slice(&width.to_le_bytes());
+    monitor.extend_from_slice(&height.to_le_bytes());
+    monitor.extend_from_slice(&96u32.to_le_bytes()); // PhysicalWidth (mm), arbitrary but spec-legal
+    monitor.extend_from_slice(&54u32.to_le_bytes()); // PhysicalHeight (mm)
+    monitor.extend_from_slice(&0u32.to_le_bytes()); // Orientation: ORIENTATION_LANDSCAPE
+    monitor.extend_from_slice(&100u32.to_le_bytes()); // DesktopScaleFactor
+    monitor.extend_from_slice(&100u32.to_le_bytes()); // DeviceScaleFactor
+
+    let mut out = Vec::with_capacity(12 + monitor.len());
+    out.extend_from_slice(&DISPLAYCONTROL_PDU_TYPE_MONITOR_LAYOUT.to_le_bytes());
+    out.extend_from_slice(&(4 + monitor.len() as u32).to_le_bytes()); // Length
+    out.extend_from_slice(&(monitor.len() as u32).to_le_bytes()); // MonitorLayoutSize
+    out.extend_from_slice(&1u32.to_le_bytes()); // NumMonitors
+    out.extend_from_slice(&monitor);
+    out
+}
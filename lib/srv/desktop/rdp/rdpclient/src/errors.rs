@@ -0,0 +1,24 @@
+// Copyright 2021 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Small helpers for constructing `RdpError`s from this crate's own code, as opposed to errors
+//! surfaced by the `rdp-rs` protocol implementation itself.
+
+use rdp::model::error::Error as RdpError;
+
+/// Builds a generic `RdpError` carrying a Teleport-specific message, for use where the failure
+/// didn't originate in the `rdp-rs` protocol layer.
+pub fn try_error(msg: &str) -> RdpError {
+    RdpError::TryError(msg.to_string())
+}
@@ -0,0 +1,198 @@
+// Copyright 2022 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Zero-copy delivery of bitmap updates to Go via a shared surface buffer and a ring of dirty-rect
+//! descriptors, modeled on the same shared-memory approach as [`crate::rdpsnd`]'s audio ring.
+//!
+//! Go allocates one `screen_width * screen_height * 4` surface (BGRA) plus a small fixed-slot ring
+//! of [`DirtyRect`] descriptors — the latter preceded in the same allocation by a
+//! [`DirtyRingHeader`], exactly as [`crate::rdpsnd::AudioRingHeader`] precedes its data region —
+//! and hands both base pointers to Rust once via `register_framebuffer`. The `global` channel's
+//! bitmap handler decodes each tile directly into the surface at its destination offset and pushes
+//! only a descriptor across CGO, advancing `DirtyRingHeader::write_cursor` in the shared memory so
+//! Go can tell a descriptor landed and read changed rectangles out of the surface with zero
+//! per-frame copies.
+
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A single changed rectangle within the shared surface. `offset` is the byte offset of
+/// `(x, y)` within the surface buffer, i.e. `(y * screen_width + x) * 4`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirtyRect {
+    pub x: u16,
+    pub y: u16,
+    pub w: u16,
+    pub h: u16,
+    pub offset: u32,
+    pub format: u8,
+}
+
+impl DirtyRect {
+    fn right(&self) -> u32 {
+        self.x as u32 + self.w as u32
+    }
+
+    fn bottom(&self) -> u32 {
+        self.y as u32 + self.h as u32
+    }
+
+    /// Whether `self` and `other` touch or overlap, and can therefore be merged into one
+    /// descriptor without expanding the dirty area by more than the gap between them.
+    fn touches(&self, other: &DirtyRect) -> bool {
+        self.x as u32 <= other.right()
+            && other.x as u32 <= self.right()
+            && self.y as u32 <= other.bottom()
+            && other.y as u32 <= self.bottom()
+    }
+
+    fn union(&self, other: &DirtyRect, screen_width: u16) -> DirtyRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        DirtyRect {
+            x,
+            y,
+            w: (right - x as u32) as u16,
+            h: (bottom - y as u32) as u16,
+            offset: (y as u32 * screen_width as u32 + x as u32) * 4,
+            format: self.format,
+        }
+    }
+}
+
+/// Merges overlapping or touching rects emitted within a single `RdpClient::read` call into the
+/// smallest set of non-overlapping descriptors, so a burst of small tile updates doesn't push one
+/// descriptor per tile.
+pub fn coalesce(mut rects: Vec<DirtyRect>, screen_width: u16) -> Vec<DirtyRect> {
+    let mut merged: Vec<DirtyRect> = Vec::with_capacity(rects.len());
+    while let Some(rect) = rects.pop() {
+        // A grown rect can touch merged rects it didn't touch before, so the union goes back on
+        // the work list to be checked against `merged` again.
+        if let Some(i) = merged.iter().position(|m| m.touches(&rect)) {
+            let m = merged.swap_remove(i);
+            rects.push(m.union(&rect, screen_width));
+        } else {
+            merged.push(rect);
+        }
+    }
+    merged
+}
+
+/// The header Go places immediately before the `DirtyRect` slot array in the ring allocation it
+/// hands to `register_framebuffer`, mirroring [`crate::rdpsnd::AudioRingHeader`]'s placement of
+/// `write_pos`/`read_pos` in the shared memory itself rather than in a private Rust-side field: Go
+/// needs to read `write_cursor` to know which slots are fresh (mod `ring_slots`, which Go already
+/// knows from having allocated the array), and a field that lived only on the Rust struct would
+/// never be visible across the CGO boundary.
+#[repr(C)]
+pub struct DirtyRingHeader {
+    pub write_cursor: AtomicUsize,
+}
+
+/// The shared surface buffer and descriptor ring Go allocated and registered via
+/// `register_framebuffer`. This is the sole producer of descriptors; Go is the sole consumer.
+pub struct SharedFramebuffer {
+    surface: *mut u8,
+    surface_len: usize,
+    screen_width: u16,
+
+    ring: *mut DirtyRect,
+    ring_slots: usize,
+}
+
+// Safety: `surface` and `ring` point at buffers that outlive this struct for the lifetime of the
+// connection. `surface` is only ever written by the single RDP read loop thread; `ring` is a
+// single-producer (us) / single-consumer (Go) structure whose cursor is atomic.
+unsafe impl Send for SharedFramebuffer {}
+unsafe impl Sync for SharedFramebuffer {}
+
+impl SharedFramebuffer {
+    /// # Safety
+    ///
+    /// `surface` must point to a live `surface_len`-byte buffer. `ring` must point to a live array
+    /// of `ring_slots` `DirtyRect`s, immediately preceded by a live [`DirtyRingHeader`]. Both must
+    /// remain valid for the lifetime of this `SharedFramebuffer`.
+    pub unsafe fn new(
+        surface: *mut u8,
+        surface_len: usize,
+        screen_width: u16,
+        ring: *mut DirtyRect,
+        ring_slots: usize,
+    ) -> Self {
+        Self {
+            surface,
+            surface_len,
+            screen_width,
+            ring,
+            ring_slots,
+        }
+    }
+
+    fn header(&self) -> &DirtyRingHeader {
+        unsafe {
+            &*(self.ring.cast::<u8>().offset(-(mem::size_of::<DirtyRingHeader>() as isize))
+                as *const DirtyRingHeader)
+        }
+    }
+
+    /// Copies a decoded BGRA tile directly into the shared surface at `(x, y)`, returning the
+    /// `DirtyRect` describing where it landed. The caller is expected to coalesce and push these
+    /// once per `RdpClient::read` call rather than per tile.
+    pub fn write_tile(&self, x: u16, y: u16, w: u16, h: u16, bgra: &[u8]) -> DirtyRect {
+        let offset = (y as usize * self.screen_width as usize + x as usize) * 4;
+        let row_bytes = w as usize * 4;
+        for row in 0..h as usize {
+            let dst_start = offset + row * self.screen_width as usize * 4;
+            let dst_end = dst_start + row_bytes;
+            let src_start = row * row_bytes;
+            let src_end = src_start + row_bytes;
+            if dst_end > self.surface_len || src_end > bgra.len() {
+                break;
+            }
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    bgra.as_ptr().add(src_start),
+                    self.surface.add(dst_start),
+                    row_bytes,
+                );
+            }
+        }
+        DirtyRect {
+            x,
+            y,
+            w,
+            h,
+            offset: offset as u32,
+            format: 0, // 0 == BGRA8888
+        }
+    }
+
+    /// Pushes one already-coalesced descriptor into the ring, advancing `write_cursor` in the
+    /// shared [`DirtyRingHeader`] with a release store so Go observes a fully-written slot and can
+    /// tell a descriptor was pushed without any other signal from Rust. If the ring is full, the
+    /// oldest, not-yet-consumed descriptor is overwritten, since a stale dirty-rect is harmless: a
+    /// later full-frame read from the surface will still pick up the change.
+    pub fn push_descriptor(&self, rect: DirtyRect) {
+        let header = self.header();
+        let cursor = header.write_cursor.load(Ordering::Relaxed);
+        let slot = cursor % self.ring_slots;
+        unsafe {
+            std::ptr::write(self.ring.add(slot), rect);
+        }
+        header.write_cursor.store(cursor.wrapping_add(1), Ordering::Release);
+    }
+}
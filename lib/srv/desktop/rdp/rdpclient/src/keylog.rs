@@ -0,0 +1,67 @@
+// Copyright 2021 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The backlog item behind this module asked for opt-in logging of TLS secrets in NSS Key Log
+//! Format (`CLIENT_RANDOM <hex> <hex>`), so a capture of an RDP session's encrypted traffic could
+//! be decrypted in Wireshark when diagnosing protocol issues. That needs a hook into the TLS
+//! handshake `x224::Client::connect` performs internally, which the vendored `rdp` crate doesn't
+//! expose at that call site, and this crate doesn't vendor that crate to patch it — there is no
+//! hook to install a keylog callback on, here or anywhere in this tree.
+//!
+//! **This module does not log anything.** An earlier version of it stood up a file writer behind
+//! `go_keylog_path`/`TELEPORT_RDP_SSLKEYLOG` and a `log_client_random` method nothing ever called,
+//! which left an empty, perpetually-unwritten file on disk for any operator who enabled it —
+//! indistinguishable from "captured nothing interesting" rather than "this can't work in this
+//! build." That file-writing half has been removed. `KeyLog::new` now only resolves whether
+//! logging was requested and logs one loud, explicit warning that it cannot be honored, so a
+//! caller's request is never silently accepted as if it did something.
+
+use std::env;
+
+/// Falls back to this environment variable when `connect_rdp` isn't given an explicit path,
+/// mirroring the `SSLKEYLOGFILE` convention most TLS libraries already support.
+const SSLKEYLOG_ENV_VAR: &str = "TELEPORT_RDP_SSLKEYLOG";
+
+/// Whether TLS key logging was requested for this connection. Always inert: see the module doc
+/// for why nothing is ever written regardless of this value.
+pub struct KeyLog(bool);
+
+impl KeyLog {
+    /// Resolves whether keylogging was requested for this connection: an explicit `path` from the
+    /// caller takes priority over `TELEPORT_RDP_SSLKEYLOG`. If either is set, warns that this
+    /// build cannot honor the request rather than silently doing nothing.
+    pub fn new(path: Option<&str>) -> Self {
+        let requested = match path {
+            Some(p) if !p.is_empty() => true,
+            _ => env::var(SSLKEYLOG_ENV_VAR)
+                .ok()
+                .filter(|p| !p.is_empty())
+                .is_some(),
+        };
+        if requested {
+            warn!(
+                "TLS key logging was requested for this connection but is not supported by this \
+                 build: no hook exists to capture TLS secrets, so no key log file will be written"
+            );
+        }
+        KeyLog(requested)
+    }
+
+    /// Whether keylogging was requested for this connection. Exposed only so a caller can
+    /// distinguish "not requested" from "requested but unsupported" if it wants to surface that
+    /// distinction further, e.g. in a status callback.
+    pub fn requested(&self) -> bool {
+        self.0
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2021 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A path type that normalizes the Windows-style paths carried over RDP's `rdpdr` channel into
+//! the forward-slash form the TDP messages and the rest of this crate expect.
+
+use crate::errors::try_error;
+use rdp::model::error::RdpResult;
+use std::ffi::CString;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UnixPath {
+    components: Vec<String>,
+}
+
+impl UnixPath {
+    pub fn last(&self) -> Option<&str> {
+        self.components.last().map(|s| s.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.to_string().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    pub fn to_cstring(&self) -> RdpResult<CString> {
+        CString::new(self.to_string())
+            .map_err(|_| try_error("path contained an interior NUL byte"))
+    }
+}
+
+impl From<String> for UnixPath {
+    fn from(s: String) -> Self {
+        UnixPath {
+            // `.` and `..` components are dropped rather than resolved: every server-supplied
+            // path that reaches here backs a `rdpdr` IRP or a symlink target, and letting a
+            // `..` walk outside the shared directory root would let the server (or anything
+            // relaying its IRPs) escape the sandboxed drive.
+            components: s
+                .split(&['/', '\\'][..])
+                .filter(|c| !c.is_empty() && *c != "." && *c != "..")
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&str> for UnixPath {
+    fn from(s: &str) -> Self {
+        UnixPath::from(s.to_string())
+    }
+}
+
+/// The raw, backslash-separated path as it appears on the wire in an IRP from the RDP server.
+#[derive(Clone, Debug)]
+pub struct WindowsPath(pub String);
+
+impl From<&WindowsPath> for UnixPath {
+    fn from(p: &WindowsPath) -> Self {
+        UnixPath::from(p.0.clone())
+    }
+}
+
+impl fmt::Display for UnixPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}", self.components.join("/"))
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2021 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implements the redial policy `spawn_rdp_reader` in lib.rs applies when an established RDP
+//! connection drops in a way that looks transient: how many times to retry and how long to back
+//! off between attempts, plus the `ConnectionState` values that get reported to Go along the way.
+//!
+//! **This module does not implement MS-RDPBCGR Server Auto-Reconnect.** The backlog item it was
+//! built for asked for session resumption after a transient network loss without re-presenting
+//! credentials, via the ARC_SC/ARC_CS cookie exchange. An earlier version of this module did parse
+//! and verify the ARC_SC cookie (HMAC-MD5 per [MS-RDPBCGR] 5.5), but nothing in this crate's
+//! `x224`/`mcs` handshake path ever captured the Save Session Info PDU needed to hand it a cookie
+//! to parse, so that code was dead and has been removed. This module is named, and its types are
+//! named, for what it actually does instead: plain redial-with-backoff. Each attempt runs the full
+//! connect-and-authenticate handshake `establish_rdp_session` already does for the first
+//! connection, re-presenting credentials every time, rather than resuming the prior session.
+//! Resuming it for real would need `sec::connect` (or an equivalent lower-level hook) to accept an
+//! ARC_CS_PRIVATE_PACKET to place in the Client Info PDU's Extended Info section, which this
+//! client's RDP stack doesn't expose.
+//!
+//! The redial loop itself lives in lib.rs's `spawn_rdp_reader`, alongside the rest of the
+//! connection lifecycle it's deciding for.
+
+use std::time::Duration;
+
+/// Governs how `spawn_rdp_reader` responds to a transient drop of the RDP connection: how many
+/// times to redial before giving up and surfacing `ConnectionState::Disconnected`, and how long to
+/// wait between attempts. Exposed through `connect_rdp` so Go (and, through it, cluster config)
+/// controls the tradeoff between masking a flaky link and failing fast.
+#[derive(Clone, Copy)]
+pub struct RedialPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RedialPolicy {
+    /// The delay before the `attempt`'th redial (1-indexed), doubling each time up to
+    /// `max_backoff`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(16);
+        self.initial_backoff
+            .saturating_mul(1u32.checked_shl(doublings).unwrap_or(u32::MAX))
+            .min(self.max_backoff)
+    }
+}
+
+/// Mirrors `CGOConnectionState` on the Go side: what `handle_connection_state` is reporting about
+/// the RDP session's health.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConnectionState {
+    Connected,
+    Reconnecting,
+    Disconnected,
+}
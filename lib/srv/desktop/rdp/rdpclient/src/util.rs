@@ -0,0 +1,21 @@
+// Copyright 2021 Gravitational, Inc
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Small formatting helpers shared across this crate's `Debug` impls.
+
+/// Formats a byte buffer for `Debug` output without dumping its full contents, which for
+/// something like file contents can be arbitrarily large and unreadable as a log line.
+pub fn vec_u8_debug(v: &[u8]) -> String {
+    format!("<{} bytes>", v.len())
+}